@@ -5,77 +5,323 @@
 //! initial point from where it should start reading. A sink should use this
 //! utility to persist the position once a block has been processed.
 
-use r2d2_redis::{
-    r2d2::{self, Pool},
-    redis::Commands,
-    RedisConnectionManager,
-};
-use std::{
-    sync::RwLock,
-    time::{Duration, Instant},
+use async_trait::async_trait;
+use mobc_redis::{redis, redis::AsyncCommands, RedisConnectionManager};
+use r2d2_postgres::{
+    postgres::{self, NoTls},
+    r2d2, PostgresConnectionManager,
 };
+use std::time::{Duration, Instant};
 
 use serde::Deserialize;
 
+use tokio::sync::RwLock;
+
 use crate::Error;
 
 pub use crate::sources::PointArg;
 
-pub(crate) trait CanStore {
-    fn read_cursor(&self) -> Result<PointArg, Error>;
-    fn write_cursor(&self, point: PointArg) -> Result<(), Error>;
+/// Errors returned by the cursor storage backends.
+///
+/// This distinguishes failures a caller can retry (transient backend
+/// trouble) from ones it can't (a corrupt persisted value), and both from
+/// the unremarkable case of no cursor having been persisted yet.
+#[derive(Debug, thiserror::Error)]
+pub enum CursorError {
+    /// The backend could not be reached or the operation failed in a way
+    /// that may clear up (connection refused, pool exhausted, timed out).
+    /// Safe to retry with backoff.
+    #[error("transient storage failure: {0}")]
+    Transient(Error),
+
+    /// The backend answered but rejected the operation in a way that a
+    /// retry won't fix (auth failure, bad config, a schema/query error).
+    #[error("unrecoverable storage failure: {0}")]
+    Permanent(Error),
+
+    /// The point could not be serialized into its persisted representation.
+    #[error("failed to serialize cursor point: {0}")]
+    Serialization(Error),
+
+    /// A point was found in storage but its persisted bytes don't parse
+    /// into a valid `PointArg`.
+    #[error("persisted cursor value is corrupt: {0}")]
+    Corrupt(String),
+
+    /// No cursor has been persisted yet.
+    #[error("no cursor has been persisted")]
+    Missing,
+}
+
+impl From<std::io::Error> for CursorError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => CursorError::Missing,
+            _ => CursorError::Transient(error.into()),
+        }
+    }
+}
+
+impl From<redis::RedisError> for CursorError {
+    fn from(error: redis::RedisError) -> Self {
+        match error.kind() {
+            // the server understood the request and refused it outright;
+            // nothing about retrying changes a bad password or a malformed
+            // client config
+            redis::ErrorKind::AuthenticationFailed | redis::ErrorKind::InvalidClientConfig => {
+                CursorError::Permanent(error.into())
+            }
+            _ => CursorError::Transient(error.into()),
+        }
+    }
+}
+
+impl From<r2d2::Error> for CursorError {
+    fn from(error: r2d2::Error) -> Self {
+        CursorError::Transient(error.into())
+    }
+}
+
+impl From<postgres::Error> for CursorError {
+    fn from(error: postgres::Error) -> Self {
+        if error.is_closed() {
+            return CursorError::Transient(error.into());
+        }
+
+        match error.code() {
+            // a SQLSTATE means the server produced a definite answer (bad
+            // auth, missing table, syntax error), not a reachability
+            // problem on our end, so retrying won't help
+            Some(_) => CursorError::Permanent(error.into()),
+            // no SQLSTATE means we never got a server response at all
+            // (connect/TLS/timeout failure), which a retry can ride out
+            None => CursorError::Transient(error.into()),
+        }
+    }
+}
+
+/// The result of attempting to persist a cursor point.
+pub(crate) enum WriteOutcome {
+    /// The point was persisted.
+    Written,
+
+    /// The write was rejected because a point at the same or a later slot
+    /// was already in storage; carries that point so the caller can catch
+    /// up to it.
+    Stale(PointArg),
+}
+
+#[async_trait]
+pub(crate) trait CanStore: Send + Sync {
+    async fn read_cursor(&self) -> Result<PointArg, CursorError>;
+    async fn write_cursor(&self, point: PointArg) -> Result<WriteOutcome, CursorError>;
+
+    /// Returns the bounded, most-recent-first history of persisted points.
+    ///
+    /// Backends that don't keep a real history fall back to a single-entry
+    /// history built from the current cursor.
+    async fn read_history(&self) -> Result<Vec<PointArg>, CursorError> {
+        match self.read_cursor().await {
+            Ok(point) => Ok(vec![point]),
+            Err(CursorError::Missing) => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Discards history above (newer than) the given slot.
+    ///
+    /// Backends that don't keep a real history have nothing to discard.
+    async fn rollback_to(&self, _slot: u64) -> Result<(), CursorError> {
+        Ok(())
+    }
+}
+
+/// Returns the slot of a point, treating the chain origin as slot zero.
+fn point_slot(point: &PointArg) -> u64 {
+    match point {
+        PointArg::Origin => 0,
+        PointArg::Specific(slot, _) => *slot,
+    }
+}
+
+fn default_history_len() -> usize {
+    20
 }
 
 /// Configuration for the file-based storage implementation
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct FileConfig {
     pub path: String,
+
+    /// How many of the most recent points to keep, to hand out as
+    /// intersection candidates after a rollback.
+    #[serde(default = "default_history_len")]
+    pub history_len: usize,
+
+    /// When true, fsync the renamed file before returning from a write, so
+    /// a crash right after can't lose the just-committed point.
+    #[serde(default)]
+    pub fsync: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct RedisConfig {
     pub url: String,
+
+    /// Namespace prefix for the history sorted set (`{key}:history`), not a
+    /// literal key holding the cursor itself. Pre-upgrade deployments that
+    /// wrote a bare serialized point directly to this key are read as a
+    /// one-time migration fallback when the history set is empty.
     pub key: String,
+
+    /// How many of the most recent points to keep, to hand out as
+    /// intersection candidates after a rollback.
+    #[serde(default = "default_history_len")]
+    pub history_len: usize,
+
+    /// When true, reject writes at a slot at or behind the stored point
+    /// instead of overwriting it, so concurrent instances sharing a key
+    /// can't rewind each other's cursor.
+    #[serde(default)]
+    pub monotonic: bool,
+}
+
+fn default_pipeline_id() -> String {
+    "default".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostgresConfig {
+    pub url: String,
+    pub table: String,
+
+    /// Identifies this pipeline's row, so a single table can back the
+    /// cursor of more than one pipeline.
+    #[serde(default = "default_pipeline_id")]
+    pub pipeline_id: String,
 }
 
 /// A cursor provider that uses the file system as the source for persistence
+#[derive(Clone)]
 pub(crate) struct FileStorage(FileConfig);
 
 /// An ephemeral cursor that lives only in memory
 pub(crate) struct MemoryStorage(PointArg);
 
-pub(crate) struct RedisStorage(RedisConfig);
+pub(crate) struct RedisStorage {
+    config: RedisConfig,
+    pool: mobc::Pool<RedisConnectionManager>,
+    cas_script: redis::Script,
+}
+
+#[derive(Clone)]
+pub(crate) struct PostgresStorage {
+    config: PostgresConfig,
+    pool: r2d2::Pool<PostgresConnectionManager<NoTls>>,
+
+    /// Set once `ensure_table` has run successfully, so the hot path stops
+    /// re-issuing `CREATE TABLE IF NOT EXISTS` after the first call.
+    table_ready: std::sync::Arc<std::sync::atomic::AtomicBool>,
+}
+
+/// Validates that `table` is safe to splice into the unparameterized SQL
+/// identifiers below (Postgres doesn't support binding table names as
+/// query parameters).
+fn validate_table_name(table: &str) -> Result<(), CursorError> {
+    let mut chars = table.chars();
+    let starts_ok = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    let rest_ok = chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if !table.is_empty() && table.len() <= 63 && starts_ok && rest_ok {
+        return Ok(());
+    }
+
+    let error = std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!(
+            "invalid Postgres cursor table name '{}': must be a simple identifier",
+            table
+        ),
+    );
+
+    Err(CursorError::Permanent(error.into()))
+}
 
 enum Storage {
     File(FileStorage),
     Memory(MemoryStorage),
     Redis(RedisStorage),
+    Postgres(PostgresStorage),
 }
 
+#[async_trait]
 impl CanStore for Storage {
-    fn read_cursor(&self) -> Result<PointArg, Error> {
+    async fn read_cursor(&self) -> Result<PointArg, CursorError> {
+        match self {
+            Storage::File(x) => x.read_cursor().await,
+            Storage::Memory(x) => x.read_cursor().await,
+            Storage::Redis(x) => x.read_cursor().await,
+            Storage::Postgres(x) => x.read_cursor().await,
+        }
+    }
+
+    async fn write_cursor(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
         match self {
-            Storage::File(x) => x.read_cursor(),
-            Storage::Memory(x) => x.read_cursor(),
-            Storage::Redis(x) => x.read_cursor(),
+            Storage::File(x) => x.write_cursor(point).await,
+            Storage::Memory(x) => x.write_cursor(point).await,
+            Storage::Redis(x) => x.write_cursor(point).await,
+            Storage::Postgres(x) => x.write_cursor(point).await,
         }
     }
 
-    fn write_cursor(&self, point: PointArg) -> Result<(), Error> {
+    async fn read_history(&self) -> Result<Vec<PointArg>, CursorError> {
         match self {
-            Storage::File(x) => x.write_cursor(point),
-            Storage::Memory(x) => x.write_cursor(point),
-            Storage::Redis(x) => x.write_cursor(point),
+            Storage::File(x) => x.read_history().await,
+            Storage::Memory(x) => x.read_history().await,
+            Storage::Redis(x) => x.read_history().await,
+            Storage::Postgres(x) => x.read_history().await,
+        }
+    }
+
+    async fn rollback_to(&self, slot: u64) -> Result<(), CursorError> {
+        match self {
+            Storage::File(x) => x.rollback_to(slot).await,
+            Storage::Memory(x) => x.rollback_to(slot).await,
+            Storage::Redis(x) => x.rollback_to(slot).await,
+            Storage::Postgres(x) => x.rollback_to(slot).await,
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
-pub enum Config {
+pub enum Backend {
     File(FileConfig),
     Memory(PointArg),
     Redis(RedisConfig),
+    Postgres(PostgresConfig),
+}
+
+fn default_flush_interval_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub backend: Backend,
+
+    /// Minimum time between cursor writes. Operators can raise this to
+    /// trade durability for write throughput.
+    #[serde(default = "default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+
+    /// If set, also flush once this many blocks have been processed since
+    /// the last flush, regardless of `flush_interval_secs`.
+    #[serde(default)]
+    pub flush_every_n_blocks: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -88,49 +334,63 @@ enum State {
 pub struct Provider {
     storage: Storage,
     state: RwLock<State>,
+    flush_interval: Duration,
+    flush_every_n_blocks: Option<u64>,
+    blocks_since_flush: std::sync::atomic::AtomicU64,
 }
 
 impl Provider {
-    fn new(config: Config) -> Self {
-        Self {
+    fn new(config: Config) -> Result<Self, CursorError> {
+        Ok(Self {
             state: RwLock::new(State::Unknown),
-            storage: match config {
-                Config::File(x) => Storage::File(FileStorage(x)),
-                Config::Memory(x) => Storage::Memory(MemoryStorage(x)),
-                Config::Redis(x) => Storage::Redis(RedisStorage(x)),
+            storage: match config.backend {
+                Backend::File(x) => Storage::File(FileStorage(x)),
+                Backend::Memory(x) => Storage::Memory(MemoryStorage(x)),
+                Backend::Redis(x) => Storage::Redis(RedisStorage::new(x)?),
+                Backend::Postgres(x) => Storage::Postgres(PostgresStorage::new(x)?),
             },
-        }
+            flush_interval: Duration::from_secs(config.flush_interval_secs),
+            flush_every_n_blocks: config.flush_every_n_blocks,
+            blocks_since_flush: std::sync::atomic::AtomicU64::new(0),
+        })
     }
 
-    pub fn initialize(config: Config) -> Self {
-        let new = Provider::new(config);
-        new.load_cursor();
+    pub async fn initialize(config: Config) -> Result<Self, CursorError> {
+        let new = Provider::new(config)?;
+        new.load_cursor().await?;
 
-        new
+        Ok(new)
     }
 
-    fn load_cursor(&self) {
-        let mut guard = self.state.write().expect("error prior to acquiring lock");
-
-        let maybe_point = self.storage.read_cursor();
-
-        if let Err(error) = &maybe_point {
-            log::warn!("failure reading cursor from storage: {}", error);
+    async fn load_cursor(&self) -> Result<(), CursorError> {
+        let mut guard = self.state.write().await;
+
+        match self.storage.read_cursor().await {
+            Ok(point) => {
+                *guard = State::AtPoint {
+                    point,
+                    reached: Instant::now(),
+                };
+
+                Ok(())
+            }
+            Err(CursorError::Missing) => {
+                log::debug!("no cursor persisted yet, starting from genesis");
+                *guard = State::Invalid;
+
+                Ok(())
+            }
+            Err(error) => {
+                log::warn!("failure reading cursor from storage: {}", error);
+                *guard = State::Invalid;
+
+                Err(error)
+            }
         }
-
-        let state = match maybe_point {
-            Ok(point) => State::AtPoint {
-                point,
-                reached: Instant::now(),
-            },
-            Err(_) => State::Invalid,
-        };
-
-        *guard = state;
     }
 
-    pub fn get_cursor(&self) -> Option<PointArg> {
-        let guard = self.state.read().expect("error prior to acquiring lock");
+    pub async fn get_cursor(&self) -> Option<PointArg> {
+        let guard = self.state.read().await;
 
         match &*guard {
             State::AtPoint { point, .. } => Some(point.clone()),
@@ -138,79 +398,598 @@ impl Provider {
         }
     }
 
-    pub fn set_cursor(&self, point: PointArg) -> Result<(), Error> {
-        let mut guard = self.state.write().unwrap();
+    pub async fn set_cursor(&self, point: PointArg) -> Result<(), CursorError> {
+        let mut guard = self.state.write().await;
+
+        let blocks_since_flush = self
+            .blocks_since_flush
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            + 1;
+
+        let due_by_blocks = matches!(
+            self.flush_every_n_blocks,
+            Some(n) if blocks_since_flush >= n
+        );
 
         let should_update = match &*guard {
-            State::AtPoint { reached, .. } => reached.elapsed() > Duration::from_secs(10),
+            State::AtPoint { reached, .. } => reached.elapsed() > self.flush_interval || due_by_blocks,
             _ => true,
         };
 
         if should_update {
-            self.storage.write_cursor(point.clone())?;
+            match self.storage.write_cursor(point.clone()).await? {
+                WriteOutcome::Written => {
+                    self.blocks_since_flush
+                        .store(0, std::sync::atomic::Ordering::Relaxed);
+
+                    *guard = State::AtPoint {
+                        reached: Instant::now(),
+                        point,
+                    };
+                }
+                WriteOutcome::Stale(current) => {
+                    self.blocks_since_flush
+                        .store(0, std::sync::atomic::Ordering::Relaxed);
+
+                    log::warn!(
+                        "cursor write rejected, a point at the same or a later slot is already persisted"
+                    );
+
+                    *guard = State::AtPoint {
+                        reached: Instant::now(),
+                        point: current,
+                    };
+                }
+            }
+        }
 
-            *guard = State::AtPoint {
-                reached: Instant::now(),
-                point,
-            };
+        Ok(())
+    }
+
+    /// Returns the stored points a source can offer during a chain-sync
+    /// intersection negotiation, most-recent first.
+    pub async fn get_intersect_candidates(&self) -> Vec<PointArg> {
+        match self.storage.read_history().await {
+            Ok(candidates) => candidates,
+            Err(error) => {
+                log::warn!("failure reading cursor history from storage: {}", error);
+                vec![]
+            }
         }
+    }
+
+    /// Notifies the provider of a chain rollback, discarding any stored
+    /// history above the given point and refreshing the in-memory cursor.
+    pub async fn rollback_to(&self, point: PointArg) -> Result<(), CursorError> {
+        self.storage.rollback_to(point_slot(&point)).await?;
+        self.load_cursor().await?;
 
         Ok(())
     }
 }
 
-impl CanStore for FileStorage {
-    fn read_cursor(&self) -> Result<PointArg, Error> {
-        let file = std::fs::read_to_string(&self.0.path)?;
-        file.parse()
+impl FileStorage {
+    /// The on-disk history, ordered by slot, most recent (highest) first.
+    fn read_lines(&self) -> Result<Vec<PointArg>, CursorError> {
+        let file = match std::fs::read_to_string(&self.0.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+
+        file.lines()
+            .map(|line| {
+                line.parse::<PointArg>()
+                    .map_err(|error: Error| CursorError::Corrupt(error.to_string()))
+            })
+            .collect()
     }
 
-    fn write_cursor(&self, point: PointArg) -> Result<(), Error> {
+    fn write_lines(&self, points: &[PointArg]) -> Result<(), CursorError> {
+        let contents = points
+            .iter()
+            .map(|point| point.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
         // we save to a tmp file and then rename to make it an atomic operation. If the
         // write were to fail, the only affected file will be the temporal one.
         let tmp_file = format!("{}.tmp", self.0.path);
-        std::fs::write(&tmp_file, point.to_string().as_bytes())?;
+        std::fs::write(&tmp_file, contents.as_bytes())?;
         std::fs::rename(&tmp_file, &self.0.path)?;
 
+        if self.0.fsync {
+            // guard against a crash between the rename and the OS flushing it to disk
+            std::fs::File::open(&self.0.path)?.sync_all()?;
+
+            // the file's own fsync doesn't make the rename durable: the directory
+            // entry pointing at the new name is a separate piece of metadata that
+            // needs its own fsync, or a crash can drop the rename despite the data
+            // already being on disk
+            let dir = std::path::Path::new(&self.0.path)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or_else(|| std::path::Path::new("."));
+            std::fs::File::open(dir)?.sync_all()?;
+        }
+
         Ok(())
     }
+
+    fn read_cursor_sync(&self) -> Result<PointArg, CursorError> {
+        self.read_lines()?
+            .into_iter()
+            .next()
+            .ok_or(CursorError::Missing)
+    }
+
+    fn write_cursor_sync(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
+        let mut history = self.read_lines()?;
+        history.insert(0, point);
+        // keep the history ordered by slot, highest first, so the retained
+        // entries are the newest ones even if a point arrives out of order
+        history.sort_by_key(|point| std::cmp::Reverse(point_slot(point)));
+        history.truncate(self.0.history_len.max(1));
+
+        self.write_lines(&history)?;
+
+        Ok(WriteOutcome::Written)
+    }
+
+    fn rollback_to_sync(&self, slot: u64) -> Result<(), CursorError> {
+        let history = self.read_lines()?;
+        let retained: Vec<_> = history
+            .into_iter()
+            .filter(|point| point_slot(point) <= slot)
+            .collect();
+
+        self.write_lines(&retained)
+    }
+}
+
+#[async_trait]
+impl CanStore for FileStorage {
+    async fn read_cursor(&self) -> Result<PointArg, CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.read_cursor_sync())
+            .await
+            .expect("file storage task panicked")
+    }
+
+    async fn write_cursor(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.write_cursor_sync(point))
+            .await
+            .expect("file storage task panicked")
+    }
+
+    async fn read_history(&self) -> Result<Vec<PointArg>, CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.read_lines())
+            .await
+            .expect("file storage task panicked")
+    }
+
+    async fn rollback_to(&self, slot: u64) -> Result<(), CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.rollback_to_sync(slot))
+            .await
+            .expect("file storage task panicked")
+    }
 }
 
+#[async_trait]
 impl CanStore for MemoryStorage {
-    fn read_cursor(&self) -> Result<PointArg, Error> {
+    async fn read_cursor(&self) -> Result<PointArg, CursorError> {
         Ok(self.0.clone())
     }
 
-    fn write_cursor(&self, _point: PointArg) -> Result<(), Error> {
+    async fn write_cursor(&self, _point: PointArg) -> Result<WriteOutcome, CursorError> {
         // No operation, memory storage doesn't persist anything
-        Ok(())
+        Ok(WriteOutcome::Written)
     }
 }
 
 impl RedisStorage {
-    pub fn get_pool(&self) -> Result<Pool<RedisConnectionManager>, Error> {
-        let manager = RedisConnectionManager::new(self.0.url.clone())?;
-        let pool = r2d2::Pool::builder().build(manager)?;
-        Ok(pool)
+    fn new(config: RedisConfig) -> Result<Self, CursorError> {
+        let client = redis::Client::open(config.url.clone())?;
+        let manager = RedisConnectionManager::new(client);
+        let pool = mobc::Pool::builder().build(manager);
+        let cas_script = redis::Script::new(include_str!("cursor_cas.lua"));
+
+        Ok(Self {
+            config,
+            pool,
+            cas_script,
+        })
+    }
+
+    /// The key of the sorted set holding the point history, scored by slot.
+    fn history_key(&self) -> String {
+        format!("{}:history", self.config.key)
+    }
+
+    fn map_pool_error(error: mobc::Error<redis::RedisError>) -> CursorError {
+        match error {
+            mobc::Error::Inner(error) => error.into(),
+            _ => CursorError::Transient(
+                redis::RedisError::from((redis::ErrorKind::IoError, "redis connection pool error"))
+                    .into(),
+            ),
+        }
+    }
+
+    /// Unconditionally pushes the point onto the history, evicting the
+    /// oldest entry once `history_len` is exceeded.
+    async fn push_unchecked(
+        &self,
+        conn: &mut mobc::Connection<RedisConnectionManager>,
+        history_key: &str,
+        data_to_write: &str,
+        point: &PointArg,
+    ) -> Result<(), CursorError> {
+        conn.zadd(history_key, data_to_write, point_slot(point))
+            .await?;
+
+        let keep = self.config.history_len.max(1) as isize;
+        conn.zremrangebyrank(history_key, 0, -keep - 1).await?;
+
+        Ok(())
+    }
+
+    /// Falls back to the pre-history format, where `self.config.key` held a
+    /// bare serialized point directly rather than a history set, so an
+    /// already-running pipeline doesn't look freshly deployed after an
+    /// upgrade. The legacy value is migrated into the history set so this
+    /// only has to happen once per key.
+    async fn read_legacy_key(
+        &self,
+        conn: &mut mobc::Connection<RedisConnectionManager>,
+    ) -> Result<Option<PointArg>, CursorError> {
+        let data: Option<String> = conn.get(&self.config.key).await?;
+
+        let Some(data) = data else {
+            return Ok(None);
+        };
+
+        let point: PointArg =
+            serde_json::from_str(&data).map_err(|error| CursorError::Corrupt(error.to_string()))?;
+
+        log::warn!(
+            "migrating Redis cursor key '{}' from the legacy single-key format to the history set; this should only happen once",
+            self.config.key
+        );
+
+        conn.zadd(self.history_key(), &data, point_slot(&point))
+            .await?;
+        let _: () = conn.del(&self.config.key).await?;
+
+        Ok(Some(point))
     }
 }
 
+#[async_trait]
 impl CanStore for RedisStorage {
-    fn read_cursor(&self) -> Result<PointArg, Error> {
-        let pool = self.get_pool()?;
-        let mut conn = pool.get()?;
-        // let data: String = conn.get("oura-cursor")?;
-        let data: String = conn.get(self.0.key.clone())?;
-        let point: PointArg = serde_json::from_str(&data)?;
-        Ok(point)
+    async fn read_cursor(&self) -> Result<PointArg, CursorError> {
+        self.read_history()
+            .await?
+            .into_iter()
+            .next()
+            .ok_or(CursorError::Missing)
     }
 
-    fn write_cursor(&self, point: PointArg) -> Result<(), Error> {
-        let pool = self.get_pool()?;
-        let mut conn = pool.get()?;
-        let data_to_write = serde_json::to_string(&point)?;
-        // conn.set("oura-cursor", data_to_write)?;
-        conn.set(self.0.key.clone(), data_to_write)?;
+    async fn write_cursor(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
+        let mut conn = self.pool.get().await.map_err(Self::map_pool_error)?;
+
+        let data_to_write =
+            serde_json::to_string(&point).map_err(|error| CursorError::Serialization(error.into()))?;
+        let history_key = self.history_key();
+
+        if !self.config.monotonic {
+            self.push_unchecked(&mut conn, &history_key, &data_to_write, &point)
+                .await?;
+
+            return Ok(WriteOutcome::Written);
+        }
+
+        let keep = self.config.history_len.max(1) as isize;
+        let result: Vec<String> = self
+            .cas_script
+            .key(&history_key)
+            .arg(&data_to_write)
+            .arg(point_slot(&point))
+            .arg(keep)
+            .invoke_async(&mut conn)
+            .await?;
+
+        match result.as_slice() {
+            [status] if status == "written" => Ok(WriteOutcome::Written),
+            [status, current] if status == "stale" => {
+                let current: PointArg = serde_json::from_str(current)
+                    .map_err(|error| CursorError::Corrupt(error.to_string()))?;
+
+                Ok(WriteOutcome::Stale(current))
+            }
+            _ => Err(CursorError::Transient(
+                redis::RedisError::from((redis::ErrorKind::ResponseError, "unexpected CAS reply")).into(),
+            )),
+        }
+    }
+
+    async fn read_history(&self) -> Result<Vec<PointArg>, CursorError> {
+        let mut conn = self.pool.get().await.map_err(Self::map_pool_error)?;
+
+        let entries: Vec<String> = conn.zrevrange(self.history_key(), 0, -1).await?;
+
+        if entries.is_empty() {
+            return Ok(match self.read_legacy_key(&mut conn).await? {
+                Some(point) => vec![point],
+                None => vec![],
+            });
+        }
+
+        entries
+            .into_iter()
+            .map(|data| {
+                serde_json::from_str(&data).map_err(|error| CursorError::Corrupt(error.to_string()))
+            })
+            .collect()
+    }
+
+    async fn rollback_to(&self, slot: u64) -> Result<(), CursorError> {
+        let mut conn = self.pool.get().await.map_err(Self::map_pool_error)?;
+
+        // strictly greater than `slot`, i.e. everything the rollback invalidated
+        let bound = format!("({}", slot);
+        conn.zremrangebyscore(self.history_key(), bound, "+inf")
+            .await?;
+
         Ok(())
     }
 }
+
+impl PostgresStorage {
+    fn new(config: PostgresConfig) -> Result<Self, CursorError> {
+        validate_table_name(&config.table)?;
+
+        let pg_config: postgres::Config = config.url.parse()?;
+        let manager = PostgresConnectionManager::new(pg_config, NoTls);
+        let pool = r2d2::Pool::builder().build(manager)?;
+
+        Ok(Self {
+            config,
+            pool,
+            table_ready: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        })
+    }
+
+    fn ensure_table(
+        &self,
+        conn: &mut r2d2::PooledConnection<PostgresConnectionManager<NoTls>>,
+    ) -> Result<(), CursorError> {
+        use std::sync::atomic::Ordering;
+
+        if self.table_ready.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (pipeline_id TEXT PRIMARY KEY, point TEXT NOT NULL)",
+                self.config.table
+            ),
+            &[],
+        )?;
+
+        self.table_ready.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    fn read_cursor_sync(&self) -> Result<PointArg, CursorError> {
+        let mut conn = self.pool.get()?;
+        self.ensure_table(&mut conn)?;
+
+        let row = conn
+            .query_opt(
+                &format!(
+                    "SELECT point FROM {} WHERE pipeline_id = $1",
+                    self.config.table
+                ),
+                &[&self.config.pipeline_id],
+            )?
+            .ok_or(CursorError::Missing)?;
+
+        let data: String = row.get(0);
+        let point: PointArg =
+            serde_json::from_str(&data).map_err(|error| CursorError::Corrupt(error.to_string()))?;
+        Ok(point)
+    }
+
+    fn write_cursor_sync(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
+        let mut conn = self.pool.get()?;
+        self.ensure_table(&mut conn)?;
+
+        let data_to_write =
+            serde_json::to_string(&point).map_err(|error| CursorError::Serialization(error.into()))?;
+
+        conn.execute(
+            &format!(
+                "INSERT INTO {0} (pipeline_id, point) VALUES ($1, $2) \
+                 ON CONFLICT (pipeline_id) DO UPDATE SET point = EXCLUDED.point",
+                self.config.table
+            ),
+            &[&self.config.pipeline_id, &data_to_write],
+        )?;
+
+        Ok(WriteOutcome::Written)
+    }
+}
+
+#[async_trait]
+impl CanStore for PostgresStorage {
+    async fn read_cursor(&self) -> Result<PointArg, CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.read_cursor_sync())
+            .await
+            .expect("postgres storage task panicked")
+    }
+
+    async fn write_cursor(&self, point: PointArg) -> Result<WriteOutcome, CursorError> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || this.write_cursor_sync(point))
+            .await
+            .expect("postgres storage task panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(slot: u64) -> PointArg {
+        PointArg::Specific(slot, format!("hash-{}", slot))
+    }
+
+    fn file_storage(history_len: usize, suffix: &str) -> FileStorage {
+        let path = std::env::temp_dir().join(format!(
+            "oura-cursor-test-{}-{}",
+            std::process::id(),
+            suffix
+        ));
+
+        FileStorage(FileConfig {
+            path: path.to_string_lossy().into_owned(),
+            history_len,
+            fsync: false,
+        })
+    }
+
+    /// Requires a local Redis instance; run with `cargo test -- --ignored`.
+    #[tokio::test]
+    #[ignore]
+    async fn read_history_migrates_a_legacy_single_key_cursor() {
+        let config = RedisConfig {
+            url: "redis://127.0.0.1/".to_string(),
+            key: format!("oura-cursor-test-legacy-{}", std::process::id()),
+            history_len: 10,
+            monotonic: false,
+        };
+
+        let storage = RedisStorage::new(config.clone()).unwrap();
+        let legacy_point = point(42);
+
+        {
+            let mut conn = storage.pool.get().await.unwrap();
+            let data = serde_json::to_string(&legacy_point).unwrap();
+            let _: () = conn.set(&config.key, &data).await.unwrap();
+        }
+
+        // the history set is still empty, so this should fall back to the
+        // legacy key instead of reporting the cursor as missing
+        let history = storage.read_history().await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(point_slot(&history[0]), 42);
+
+        // the fallback should have migrated the point into the history set,
+        // so a second read no longer needs the legacy key at all
+        let _: () = {
+            let mut conn = storage.pool.get().await.unwrap();
+            conn.del(&config.key).await.unwrap()
+        };
+
+        let history_again = storage.read_history().await.unwrap();
+        assert_eq!(point_slot(&history_again[0]), 42);
+    }
+
+    #[test]
+    fn write_cursor_prepends_and_evicts_oldest() {
+        let storage = file_storage(2, "evict");
+
+        storage.write_cursor_sync(point(1)).unwrap();
+        storage.write_cursor_sync(point(2)).unwrap();
+        storage.write_cursor_sync(point(3)).unwrap();
+
+        let slots: Vec<_> = storage.read_lines().unwrap().iter().map(point_slot).collect();
+        assert_eq!(slots, vec![3, 2]);
+
+        std::fs::remove_file(&storage.0.path).ok();
+    }
+
+    #[test]
+    fn rollback_to_discards_points_above_slot() {
+        let storage = file_storage(10, "rollback");
+
+        storage.write_cursor_sync(point(30)).unwrap();
+        storage.write_cursor_sync(point(20)).unwrap();
+        storage.write_cursor_sync(point(10)).unwrap();
+
+        storage.rollback_to_sync(20).unwrap();
+
+        let slots: Vec<_> = storage.read_lines().unwrap().iter().map(point_slot).collect();
+        assert_eq!(slots, vec![20, 10]);
+
+        std::fs::remove_file(&storage.0.path).ok();
+    }
+
+    #[test]
+    fn read_cursor_is_missing_when_no_file_exists() {
+        let storage = file_storage(5, "missing");
+
+        assert!(matches!(
+            storage.read_cursor_sync(),
+            Err(CursorError::Missing)
+        ));
+    }
+
+    fn memory_provider(flush_interval: Duration, flush_every_n_blocks: Option<u64>) -> Provider {
+        Provider {
+            storage: Storage::Memory(MemoryStorage(point(0))),
+            state: RwLock::new(State::AtPoint {
+                point: point(0),
+                reached: Instant::now(),
+            }),
+            flush_interval,
+            flush_every_n_blocks,
+            blocks_since_flush: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn set_cursor_holds_off_until_the_flush_interval_elapses() {
+        let provider = memory_provider(Duration::from_secs(3600), None);
+
+        provider.set_cursor(point(1)).await.unwrap();
+
+        // neither the interval has elapsed nor is a block threshold set, so
+        // the in-memory cursor should still be at the original point
+        let slot = point_slot(&provider.get_cursor().await.unwrap());
+        assert_eq!(slot, 0);
+    }
+
+    #[tokio::test]
+    async fn set_cursor_flushes_once_the_block_threshold_is_reached() {
+        let provider = memory_provider(Duration::from_secs(3600), Some(3));
+
+        provider.set_cursor(point(1)).await.unwrap();
+        provider.set_cursor(point(2)).await.unwrap();
+        assert_eq!(point_slot(&provider.get_cursor().await.unwrap()), 0);
+
+        provider.set_cursor(point(3)).await.unwrap();
+        assert_eq!(point_slot(&provider.get_cursor().await.unwrap()), 3);
+    }
+
+    #[tokio::test]
+    async fn set_cursor_resets_the_block_counter_after_a_flush() {
+        let provider = memory_provider(Duration::from_secs(3600), Some(2));
+
+        provider.set_cursor(point(1)).await.unwrap();
+        provider.set_cursor(point(2)).await.unwrap();
+        assert_eq!(point_slot(&provider.get_cursor().await.unwrap()), 2);
+
+        // the counter should have reset to zero on the flush above, so a
+        // single further call isn't enough to reach the threshold again
+        provider.set_cursor(point(3)).await.unwrap();
+        assert_eq!(point_slot(&provider.get_cursor().await.unwrap()), 2);
+    }
+}